@@ -3,21 +3,49 @@
 use std::fs;
 use std::env;
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process;
 
 /// Config is the base structure for retrieving input from the user.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Config {
     /// The string you want to search/query for in a given file.
     pub query: String,
-    /// The filepath you want to run the query against.
-    pub filename: String,
-    /// Taken from the environment variable `CASE_INSENSITIVE`.
-    /// If this environment variable exists the search is case insensitive.
+    /// The filepaths you want to run the query against. A path that names a
+    /// directory is traversed recursively for regular files.
+    pub filename: Vec<String>,
+    /// Set by the `-i`/`--ignore-case` flag, or the `CASE_INSENSITIVE`
+    /// environment variable when neither flag is given.
     pub case_insensitive: bool,
+    /// Set by the `-o`/`--output` flag. When present, results are written
+    /// to this file instead of stdout.
+    pub output: Option<String>,
+}
+
+/// A single match produced by `search` or `search_case_insensitive`.
+#[derive(Debug, PartialEq)]
+pub struct Match<'a> {
+    /// The 0-indexed line number the match was found on.
+    pub line_number: usize,
+    /// The byte offset into `line` where the match begins.
+    pub offset: usize,
+    /// The full text of the line the match was found on.
+    pub line: &'a str,
 }
 
 impl Config {
     /// Creating a new config from standard input.
+    ///
+    /// Recognised flags are `-i`/`--ignore-case` to force a case-insensitive
+    /// search, `-o`/`--output <file>` to write results to a file instead of
+    /// stdout, and `-h`/`--help` to print usage and exit. Flags may appear
+    /// before, between, or after the positional `query` and `filename`
+    /// arguments. The first positional argument is the query; every
+    /// positional argument after that is a path to search, so multiple
+    /// files (or directories) may be given. When no flag is given, case
+    /// sensitivity falls back to the `CASE_INSENSITIVE` environment
+    /// variable.
+    ///
     /// # Example
     /// ```
     /// use std::env;
@@ -29,37 +57,83 @@ impl Config {
     ///     process::exit(1);
     /// });
     /// ```
-    pub fn new(mut args: env::Args) -> Result<Config, &'static str> {
+    pub fn new(mut args: env::Args) -> Result<Config, String> {
         // Skip filename as it its the first item in the `Args` vector
         // iterator.
         args.next();
 
-        // The query we want to search for.
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Did not get a query string."),
-        };
+        match parse_args(args)? {
+            ParsedArgs::Config(config) => Ok(config),
+            ParsedArgs::Help => {
+                print_usage();
+                process::exit(0);
+            }
+        }
+    }
+}
 
-        // The filename we want to query.
-        let filename = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Did not get a filename."),
-        };
+/// The result of parsing arguments, before the process-exiting side effect
+/// of `--help` is applied. Split out from `Config::new` so the parser
+/// itself can be exercised with plain string iterators in tests.
+#[derive(Debug, PartialEq)]
+enum ParsedArgs {
+    Config(Config),
+    Help,
+}
 
-        // See if case sensitivity is enabled.
-        let case_insensitive = env::var("CASE_INSENSITIVE").is_err();
+/// Parse `Config` fields out of an iterator of arguments, not including the
+/// program name.
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<ParsedArgs, String> {
+    let mut query: Option<String> = None;
+    let mut filename: Vec<String> = Vec::new();
+    let mut output: Option<String> = None;
+    // Fall back to the environment variable when no flag is given.
+    let mut case_insensitive = env::var("CASE_INSENSITIVE").is_ok();
 
-        Ok(Config {
-            query,
-            filename,
-            case_insensitive,
-        })
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-i" | "--ignore-case" => case_insensitive = true,
+            "-o" | "--output" => {
+                match args.next() {
+                    Some(value) if !value.starts_with('-') => output = Some(value),
+                    _ => return Err(format!("'{}' requires a file argument", arg)),
+                }
+            }
+            "-h" | "--help" => return Ok(ParsedArgs::Help),
+            _ if arg.starts_with('-') => {
+                return Err(format!("Unknown flag '{}'", arg));
+            }
+            _ if query.is_none() => query = Some(arg),
+            _ => filename.push(arg),
+        }
+    }
+
+    let query = query.ok_or_else(|| "Did not get a query string.".to_string())?;
+    if filename.is_empty() {
+        return Err("Did not get a filename.".to_string());
     }
+
+    Ok(ParsedArgs::Config(Config {
+        query,
+        filename,
+        case_insensitive,
+        output,
+    }))
+}
+
+/// Print the command-line usage for `rust_grep` to stdout.
+fn print_usage() {
+    println!("Usage: rust_grep [OPTIONS] <query> <path>...");
+    println!();
+    println!("Options:");
+    println!("  -i, --ignore-case    Search case-insensitively");
+    println!("  -o, --output <file>  Write results to <file> instead of stdout");
+    println!("  -h, --help           Print this help message and exit");
 }
 
 /// The entrypoint that executes the search based on the `Config` recieved.
-/// If `CASE_INSENSITIVE` environment variable is defined run executes
-/// `search_case_insensitive`. Otherwise it executes `search`.
+/// If `config.case_insensitive` is set `run` executes `search_case_insensitive`.
+/// Otherwise it executes `search`.
 ///
 /// # Example
 /// ```
@@ -82,72 +156,220 @@ impl Config {
 /// }
 /// ```
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    // Read the file to string.
-    let contents = fs::read_to_string(config.filename)?;
-
-    // Get the results of the search based in case sensitivity.
-    let results = if config.case_insensitive {
-        search(&config.query, &contents)
-    } else {
-        search_case_insensitive(&config.query, &contents)
-    };
+    // Walk every given path, expanding directories into the regular files
+    // they contain.
+    let mut files = Vec::new();
+    for path in &config.filename {
+        collect_files(Path::new(path), &mut files);
+    }
+
+    let multiple_sources = files.len() > 1;
+
+    let mut formatted = Vec::new();
+    for path in &files {
+        // Unreadable or non-UTF-8 (binary) files are skipped with a warning
+        // rather than aborting the whole search.
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("Warning: skipping '{}': {}", path.display(), err);
+                continue;
+            }
+        };
+
+        // Get the results of the search based in case sensitivity.
+        let results = if config.case_insensitive {
+            search_case_insensitive(&config.query, &contents)
+        } else {
+            search(&config.query, &contents)
+        };
+
+        // Format the results asociated by the line location in the file,
+        // prefixing with the filename when more than one source is involved.
+        for m in results {
+            if multiple_sources {
+                formatted.push(format!("{}:{}: {}", path.display(), m.line_number, m.line));
+            } else {
+                formatted.push(format!("{}: {}", m.line_number, m.line));
+            }
+        }
+    }
 
-    // Print the results asociated by the line location in the file.
-    for (i, line) in results {
-        println!("{}: {}", i, line);
+    match config.output {
+        // Write the results to the given file, creating/truncating it.
+        Some(path) => {
+            let mut out = formatted.join("\n");
+            if !formatted.is_empty() {
+                out.push('\n');
+            }
+            fs::write(path, out)?;
+        }
+        // Otherwise print the results to stdout.
+        None => {
+            for line in formatted {
+                println!("{}", line);
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Recursively collect every regular file reachable from `path` into
+/// `files`. If `path` is itself a regular file it is pushed directly.
+/// Paths that cannot be read (missing files, unreadable directories) are
+/// skipped with a warning to stderr rather than failing the whole walk.
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            eprintln!("Warning: skipping '{}': {}", path.display(), err);
+            return;
+        }
+    };
+
+    if metadata.is_dir() {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                eprintln!("Warning: skipping '{}': {}", path.display(), err);
+                return;
+            }
+        };
+
+        for entry in entries {
+            match entry {
+                Ok(entry) => collect_files(&entry.path(), files),
+                Err(err) => eprintln!("Warning: skipping entry in '{}': {}", path.display(), err),
+            }
+        }
+    } else if metadata.is_file() {
+        files.push(path.to_path_buf());
+    }
+}
+
 /// Search for `query` in the `contents` of the file recieved by `Config`.
-/// Returns a Vector containing tuples of usize and &str which represent the
-/// line number and line where the query matched.
-pub fn search<'a>(query: &'a str, contents: &'a str) -> Vec<(usize, &'a str)> {
+/// Returns a Vector of `Match`, one per line that contains `query`, with the
+/// byte offset of the match recorded alongside the line number and text.
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
     contents
         .lines()
         // Enumerate to get line numbers.
         .enumerate()
-        // Filter to later collect line and number into a Vec.
-        .filter(|(_, line)| line.contains(query))
+        // Find the match offset and collect it alongside the line.
+        .filter_map(|(line_number, line)| {
+            line.find(query).map(|offset| Match {
+                line_number,
+                offset,
+                line,
+            })
+        })
         .collect()
 }
 
 /// Search for `query` in the `contents` of the file recieved by `Config`.
-/// Returns a Vector containing tuples of usize and &str which represent the
-/// line number and line where the query matched case insensitively.
-pub fn search_case_insensitive<'a>(query: &'a str, contents: &'a str)
-    -> Vec<(usize, &'a str)> {
+/// Returns a Vector of `Match`, one per line that contains `query` case
+/// insensitively, with the byte offset of the match recorded alongside the
+/// line number and text.
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+    let query = query.to_lowercase();
     contents
         .lines()
         // Enumerate to get line numbers.
         .enumerate()
-        // Filter to later collect line and number into a Vec.
-        .filter(|(_, line)| {
-            // Do the search insensitively.
-            line.to_lowercase().contains(&query.to_lowercase())
+        // Find the match offset insensitively and collect it alongside the line.
+        .filter_map(|(line_number, line)| {
+            find_case_insensitive(line, &query).map(|offset| Match {
+                line_number,
+                offset,
+                line,
+            })
         })
         .collect()
 }
 
+/// Find the byte offset in `line` where `query_lower` (already lowercased)
+/// first matches case-insensitively. Searching in `line.to_lowercase()`
+/// directly is not safe to index back into `line`, since case folding can
+/// change a character's UTF-8 byte length (e.g. `İ` folds to `i` followed
+/// by a combining dot above). Walking the original char boundaries keeps
+/// the returned offset valid for indexing into `line` itself.
+fn find_case_insensitive(line: &str, query_lower: &str) -> Option<usize> {
+    // An empty query matches at the start of every line, even an empty one.
+    if query_lower.is_empty() {
+        return Some(0);
+    }
+    line.char_indices()
+        .find(|(i, _)| line[*i..].to_lowercase().starts_with(query_lower))
+        .map(|(i, _)| i)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Build a `String` iterator from string literals, the shape
+    /// `parse_args` expects (no leading program name).
+    fn args(items: &[&str]) -> impl Iterator<Item = String> {
+        items.iter().map(|s| s.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn parse_args_allows_flags_interspersed_with_positionals() {
+        let parsed = parse_args(args(&["-i", "duct", "-o", "out.txt", "file.txt"])).unwrap();
+        assert_eq!(
+            parsed,
+            ParsedArgs::Config(Config {
+                query: "duct".to_string(),
+                filename: vec!["file.txt".to_string()],
+                case_insensitive: true,
+                output: Some("out.txt".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_args_help_short_circuits_before_collecting_a_query() {
+        // `--help` alone, with no query or filename, must not fail with
+        // "Did not get a query string." - it should win immediately.
+        assert_eq!(parse_args(args(&["--help"])), Ok(ParsedArgs::Help));
+        assert_eq!(parse_args(args(&["-h"])), Ok(ParsedArgs::Help));
+    }
+
+    #[test]
+    fn parse_args_rejects_unknown_flags() {
+        let err = parse_args(args(&["duct", "file.txt", "--bogus"])).unwrap_err();
+        assert_eq!(err, "Unknown flag '--bogus'");
+    }
+
+    #[test]
+    fn parse_args_rejects_output_flag_followed_by_another_flag() {
+        // `-o` immediately followed by another flag is a missing argument,
+        // not a license to treat that flag as the output path.
+        let err = parse_args(args(&["duct", "file.txt", "-o", "-i"])).unwrap_err();
+        assert_eq!(err, "'-o' requires a file argument");
+    }
+
+    #[test]
+    fn parse_args_rejects_output_flag_with_nothing_after_it() {
+        let err = parse_args(args(&["duct", "file.txt", "-o"])).unwrap_err();
+        assert_eq!(err, "'-o' requires a file argument");
+    }
+
     #[test]
     fn one_result() {
         let query = "duct";
         let contents = "\
-Rust 
+Rust
 safe, fast, productive.
 Pic three.";
         assert_eq!(
-            vec![(1, "safe, fast, productive.")],
+            vec![Match { line_number: 1, offset: 15, line: "safe, fast, productive." }],
             search(query, contents)
         );
         let query = "fast";
         assert_eq!(
-            vec![(1, "safe, fast, productive.")],
+            vec![Match { line_number: 1, offset: 6, line: "safe, fast, productive." }],
             search(query, contents)
         );
     }
@@ -160,14 +382,120 @@ Rust
 safe, fast, productive.
 Pic three.";
         assert_eq!(
-            vec![(0, "Rust")],
+            vec![Match { line_number: 0, offset: 0, line: "Rust" }],
             search_case_insensitive(query, contents)
         );
 
         let query = "FaSt";
         assert_eq!(
-            vec![(1, "safe, fast, productive.")],
+            vec![Match { line_number: 1, offset: 6, line: "safe, fast, productive." }],
             search_case_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn case_insensitive_offset_survives_byte_length_changing_case_fold() {
+        // 'İ' (U+0130, 2 bytes) lowercases to 'i' + COMBINING DOT ABOVE (3
+        // bytes), so the offset found in `line.to_lowercase()` does not
+        // line up with `line` itself. The real match starts at byte 3.
+        let line = "İ fast";
+        let query = "fast";
+        assert_eq!(
+            vec![Match { line_number: 0, offset: 3, line }],
+            search_case_insensitive(query, line)
+        );
+    }
+
+    #[test]
+    fn collect_files_walks_directories_recursively() {
+        let dir = env::temp_dir().join("rust_grep_test_collect_files");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a.txt"), "hello").unwrap();
+        fs::write(dir.join("nested").join("b.txt"), "world").unwrap();
+
+        let mut files = Vec::new();
+        collect_files(&dir, &mut files);
+
+        assert_eq!(files.len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn run_prefixes_output_with_filename_for_multiple_sources() {
+        let dir = env::temp_dir().join("rust_grep_test_run_prefix");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file_a = dir.join("a.txt");
+        let file_b = dir.join("b.txt");
+        fs::write(&file_a, "a match here").unwrap();
+        fs::write(&file_b, "no hits").unwrap();
+        let output = dir.join("out.txt");
+
+        let config = Config {
+            query: "match".to_string(),
+            filename: vec![
+                file_a.to_str().unwrap().to_string(),
+                file_b.to_str().unwrap().to_string(),
+            ],
+            case_insensitive: false,
+            output: Some(output.to_str().unwrap().to_string()),
+        };
+
+        run(config).unwrap();
+
+        let written = fs::read_to_string(&output).unwrap();
+        assert!(written.contains(&format!("{}:0: a match here", file_a.display())));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn run_writes_matches_to_output_file_for_a_single_source() {
+        let dir = env::temp_dir().join("rust_grep_test_run_output_single");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.txt");
+        fs::write(&file, "alpha\nbeta\n").unwrap();
+        let output = dir.join("out.txt");
+
+        let config = Config {
+            query: "beta".to_string(),
+            filename: vec![file.to_str().unwrap().to_string()],
+            case_insensitive: false,
+            output: Some(output.to_str().unwrap().to_string()),
+        };
+
+        run(config).unwrap();
+
+        // Single-source output is not filename-prefixed, unlike multi-source.
+        assert_eq!(fs::read_to_string(&output).unwrap(), "1: beta\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn run_writes_an_empty_output_file_when_there_are_no_matches() {
+        let dir = env::temp_dir().join("rust_grep_test_run_output_empty");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.txt");
+        fs::write(&file, "alpha\nbeta\n").unwrap();
+        let output = dir.join("out.txt");
+
+        let config = Config {
+            query: "nonexistent".to_string(),
+            filename: vec![file.to_str().unwrap().to_string()],
+            case_insensitive: false,
+            output: Some(output.to_str().unwrap().to_string()),
+        };
+
+        run(config).unwrap();
+
+        // No matches means no trailing newline should be written either.
+        assert_eq!(fs::read_to_string(&output).unwrap(), "");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }